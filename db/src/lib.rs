@@ -4,18 +4,36 @@ mod schema;
 
 use crate::schema::weather_log;
 use crate::schema::subscribers;
+use crate::schema::slack_subscribers;
 use crate::schema::weather_log::dsl::*;
 use crate::schema::subscribers::dsl::*;
-use chrono::Utc;
+use chrono::{DateTime, Duration, Utc};
 use diesel::prelude::*;
-use diesel::sqlite::SqliteConnection;
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::fmt::Display;
+use tracing::debug;
 
-/// Connect to SQLite database
-pub fn establish_connection(database_url: &str) -> SqliteConnection {
-    println!("Connecting to {}", database_url);
-    SqliteConnection::establish(database_url)
+#[cfg(all(feature = "sqlite", feature = "postgres"))]
+compile_error!("features \"sqlite\" and \"postgres\" are mutually exclusive, enable exactly one");
+
+#[cfg(not(any(feature = "sqlite", feature = "postgres")))]
+compile_error!("enable exactly one of the \"sqlite\" or \"postgres\" features");
+
+/// The diesel backend this build was compiled for, selected at compile time via the
+/// `sqlite`/`postgres` cargo features so the rest of the crate can stay backend-agnostic.
+#[cfg(feature = "sqlite")]
+pub type DbConnection = diesel::sqlite::SqliteConnection;
+
+#[cfg(feature = "postgres")]
+pub type DbConnection = diesel::pg::PgConnection;
+
+/// Connect to the configured database backend
+pub fn establish_connection(database_url: &str) -> DbConnection {
+    // Called on every reading (notification fan-out, Slack fan-out, each DB save, /stats), so
+    // this stays at `debug` rather than `info` to avoid spamming stdout on the default filter.
+    debug!(%database_url, "connecting to database");
+    DbConnection::establish(database_url)
         .expect(&format!("Error connecting to {}", database_url))
 }
 
@@ -39,7 +57,7 @@ pub struct NewSubscriber {
 }
 
 /// Save new subscriber to the database if he does not already exist
-pub fn subscribe(chat_id: i64, connection: &SqliteConnection) -> Result<NewSubscriber, &str> {
+pub fn subscribe(chat_id: i64, connection: &DbConnection) -> Result<NewSubscriber, &str> {
     // let's find if there are any existing subscribers
     let existing_subscriber = subscribers.filter(telegram_chat_id.eq(chat_id)).first::<Subscriber>(connection);
 
@@ -57,12 +75,12 @@ pub fn subscribe(chat_id: i64, connection: &SqliteConnection) -> Result<NewSubsc
 }
 
 /// Deletes a subscriber from the database
-pub fn unsubscribe(chat_id: i64, connection: &SqliteConnection) -> QueryResult<usize> {
+pub fn unsubscribe(chat_id: i64, connection: &DbConnection) -> QueryResult<usize> {
     diesel::delete(subscribers.filter(telegram_chat_id.eq(chat_id))).execute(connection)
 }
 
 /// returns all existing subscribers
-pub fn get_all_subscribers(connection: &SqliteConnection) -> Vec<i64> {
+pub fn get_all_subscribers(connection: &DbConnection) -> Vec<i64> {
     subscribers.load::<Subscriber>(connection)
         .unwrap_or_default()
         .iter()
@@ -70,6 +88,53 @@ pub fn get_all_subscribers(connection: &SqliteConnection) -> Vec<i64> {
         .collect()
 }
 
+/// This struct represents an existing Slack subscriber, mirroring [Subscriber] for the
+/// Telegram channel.
+#[derive(Queryable)]
+pub struct SlackSubscriber {
+    id: i32,
+    slack_user_id: String,
+}
+
+/// A new Slack subscriber, mirroring [NewSubscriber] for the Telegram channel.
+#[derive(Insertable)]
+#[table_name = "slack_subscribers"]
+pub struct NewSlackSubscriber {
+    slack_user_id: String,
+}
+
+/// Save a new Slack subscriber to the database if they do not already exist
+pub fn subscribe_slack(user_id: &str, connection: &DbConnection) -> Result<NewSlackSubscriber, &'static str> {
+    let existing_subscriber = slack_subscribers::table
+        .filter(slack_subscribers::slack_user_id.eq(user_id))
+        .first::<SlackSubscriber>(connection);
+
+    if let Err(diesel::NotFound) = existing_subscriber {
+        let subscriber = NewSlackSubscriber { slack_user_id: user_id.to_string() };
+
+        match diesel::insert_into(slack_subscribers::table).values(&subscriber).execute(connection) {
+            Ok(_) => Ok(subscriber),
+            Err(_) => Err("Error while saving new Slack subscriber to DB"),
+        }
+    } else {
+        Err("The Slack subscriber already exists")
+    }
+}
+
+/// Deletes a Slack subscriber from the database
+pub fn unsubscribe_slack(user_id: &str, connection: &DbConnection) -> QueryResult<usize> {
+    diesel::delete(slack_subscribers::table.filter(slack_subscribers::slack_user_id.eq(user_id))).execute(connection)
+}
+
+/// returns all existing Slack subscribers
+pub fn get_all_slack_subscribers(connection: &DbConnection) -> Vec<String> {
+    slack_subscribers::table.load::<SlackSubscriber>(connection)
+        .unwrap_or_default()
+        .into_iter()
+        .map(|s| s.slack_user_id)
+        .collect()
+}
+
 
 /// WeatherMessage representation for read DB queries
 /// This structure represents weather data that we read from SQLlite
@@ -80,6 +145,7 @@ pub struct WeatherMessage {
     pub temp: f32,
     pub pressure: f32,
     pub humidity: f32,
+    pub sensor: String,
 }
 
 /// WeatherMessage respresentation for insert DB queries
@@ -91,6 +157,7 @@ pub struct NewWeatherMessage {
     temp: f32,
     pressure: f32,
     humidity: f32,
+    sensor: String,
 }
 
 /// Raw WeatherMessage that comves from the edge device via MQTT in a JSON format
@@ -128,6 +195,21 @@ impl EspWeatherMessage {
         }
     }
 
+    /// Temperature in degrees Celsius
+    pub fn temp(&self) -> f32 {
+        self.temp
+    }
+
+    /// Relative humidity in percent
+    pub fn humidity(&self) -> f32 {
+        self.humidity
+    }
+
+    /// Pressure in Pascals
+    pub fn pressure(&self) -> f32 {
+        self.pressure
+    }
+
     /// Converts pressure from Pascals to millimetres of mercury
     pub fn pressure_to_enoji(&self) -> &str {
         const PA_TO_MM_MERCURY: f32 = 133.322;
@@ -146,29 +228,56 @@ impl EspWeatherMessage {
 /// A few convenience methods for constructing and saving weather messages
 impl NewWeatherMessage {
     /// Create a new WeatherMessage that later can be saved to database
-    pub fn new(tmp: f32, press: f32, hum: f32) -> NewWeatherMessage {
+    pub fn new(tmp: f32, press: f32, hum: f32, sensor_id: &str) -> NewWeatherMessage {
         NewWeatherMessage {
             timestamp: Utc::now().to_rfc3339(),
             temp: tmp,
             pressure: press,
             humidity: hum,
+            sensor: sensor_id.to_string(),
         }
     }
 
-    /// Converts `EspWeatherMessage` to `NewWeatherMessage` that can be saved to DB.
+    /// Converts `EspWeatherMessage` to `NewWeatherMessage` that can be saved to DB, tagging it
+    /// with the sensor/location id derived from the MQTT topic it arrived on.
     /// This function should be used to convert all new incoming weather messages
-    pub fn from_esp_weather_message(msg: &EspWeatherMessage) -> NewWeatherMessage {
-        NewWeatherMessage::new(msg.temp, msg.humidity, msg.humidity)
+    pub fn from_esp_weather_message(msg: &EspWeatherMessage, sensor_id: &str) -> NewWeatherMessage {
+        NewWeatherMessage::new(msg.temp, msg.pressure, msg.humidity, sensor_id)
+    }
+
+    /// Sensor/location id the reading was tagged with
+    pub fn sensor(&self) -> &str {
+        &self.sensor
     }
    
     /// Saves message to database
-    pub fn save_to_db(&self, connection: &SqliteConnection) -> QueryResult<usize> {
+    pub fn save_to_db(&self, connection: &DbConnection) -> QueryResult<usize> {
         let result = diesel::insert_into(weather_log::table)
             .values(self)
             .execute(connection);
-        
+
         result
     }
+
+    /// RFC3339 timestamp of the reading
+    pub fn timestamp(&self) -> &str {
+        &self.timestamp
+    }
+
+    /// Temperature in degrees Celsius
+    pub fn temp(&self) -> f32 {
+        self.temp
+    }
+
+    /// Pressure in Pascals
+    pub fn pressure(&self) -> f32 {
+        self.pressure
+    }
+
+    /// Relative humidity in percent
+    pub fn humidity(&self) -> f32 {
+        self.humidity
+    }
 }
 
 /// Display trait is used to convert `EspWeatherMessage` to string.
@@ -191,11 +300,97 @@ impl Display for EspWeatherMessage {
 }
 
 /// Returns all existing weather messages ordered from last to first
-pub fn get_all_weather_messages(connection: &SqliteConnection) -> Vec<WeatherMessage> {
+pub fn get_all_weather_messages(connection: &DbConnection) -> Vec<WeatherMessage> {
     let weather_logs = weather_log.order(timestamp.desc()).load::<WeatherMessage>(connection);
     weather_logs.unwrap()
 }
 
+/// Returns the most recent weather reading, if any have been saved yet
+pub fn get_latest_reading(connection: &DbConnection) -> QueryResult<Option<WeatherMessage>> {
+    weather_log.order(timestamp.desc()).first::<WeatherMessage>(connection).optional()
+}
+
+/// Min/max/mean/median for one metric over a single calendar day
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Stat {
+    pub min: f32,
+    pub max: f32,
+    pub mean: f32,
+    pub median: Option<f32>,
+}
+
+impl Stat {
+    /// Builds a `Stat` from a metric's readings for one day. `values` need not be sorted.
+    /// Returns `None` if `values` is empty.
+    fn from_values(values: &mut Vec<f32>) -> Option<Stat> {
+        if values.is_empty() {
+            return None;
+        }
+
+        values.sort_by(|a, b| a.partial_cmp(b).unwrap());
+
+        let min = values[0];
+        let max = values[values.len() - 1];
+        let mean = values.iter().sum::<f32>() / values.len() as f32;
+        let median = if values.len() % 2 == 1 {
+            values[values.len() / 2]
+        } else {
+            (values[values.len() / 2 - 1] + values[values.len() / 2]) / 2.0
+        };
+
+        Some(Stat { min, max, mean, median: Some(median) })
+    }
+}
+
+/// Per-day aggregated weather statistics
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct WeatherStats {
+    pub day: String,
+    pub temp_stats: Stat,
+    pub pressure_stats: Stat,
+    pub humidity_stats: Stat,
+}
+
+/// Aggregates `weather_log` readings from the last `n_days` into daily min/max/mean/median
+/// statistics for temperature, pressure and humidity. Days with no readings are skipped.
+pub fn median_weather(n_days: i64, connection: &DbConnection) -> QueryResult<Vec<WeatherStats>> {
+    let cutoff = Utc::now() - Duration::days(n_days);
+    let logs = weather_log.order(timestamp.asc()).load::<WeatherMessage>(connection)?;
+
+    let mut by_day: BTreeMap<String, (Vec<f32>, Vec<f32>, Vec<f32>)> = BTreeMap::new();
+
+    for log in logs {
+        let parsed: DateTime<Utc> = match log.timestamp.parse() {
+            Ok(ts) => ts,
+            Err(_) => continue,
+        };
+
+        if parsed < cutoff {
+            continue;
+        }
+
+        let day = parsed.format("%Y-%m-%d").to_string();
+        let entry = by_day.entry(day).or_insert_with(|| (Vec::new(), Vec::new(), Vec::new()));
+        entry.0.push(log.temp);
+        entry.1.push(log.pressure);
+        entry.2.push(log.humidity);
+    }
+
+    let stats = by_day
+        .into_iter()
+        .filter_map(|(day, (mut temps, mut pressures, mut humidities))| {
+            Some(WeatherStats {
+                day,
+                temp_stats: Stat::from_values(&mut temps)?,
+                pressure_stats: Stat::from_values(&mut pressures)?,
+                humidity_stats: Stat::from_values(&mut humidities)?,
+            })
+        })
+        .collect();
+
+    Ok(stats)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -212,7 +407,7 @@ mod tests {
     #[test]
     fn test_insert_and_delete_weather_message() {
         let connection = establish_connection(TEST_DB);
-        let new_weather_message = NewWeatherMessage::new(25.0, 10000.0, 55.0);
+        let new_weather_message = NewWeatherMessage::new(25.0, 10000.0, 55.0, "attic");
         let result = diesel::insert_into(weather_log::table)
             .values(&new_weather_message)
             .execute(&connection);
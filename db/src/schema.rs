@@ -0,0 +1,30 @@
+table! {
+    subscribers (id) {
+        id -> Integer,
+        telegram_chat_id -> BigInt,
+    }
+}
+
+table! {
+    slack_subscribers (id) {
+        id -> Integer,
+        slack_user_id -> Text,
+    }
+}
+
+table! {
+    weather_log (id) {
+        id -> Integer,
+        timestamp -> Text,
+        temp -> Float,
+        pressure -> Float,
+        humidity -> Float,
+        sensor -> Text,
+    }
+}
+
+allow_tables_to_appear_in_same_query!(
+    subscribers,
+    slack_subscribers,
+    weather_log,
+);
@@ -0,0 +1,100 @@
+use crate::AlertSettings;
+use db::EspWeatherMessage;
+
+/// Per-metric armed/disarmed state, tracked across readings so a value hovering right at a
+/// threshold doesn't re-fire an alert on every MQTT tick.
+#[derive(Default, Clone)]
+pub struct AlertState {
+    temp_armed: bool,
+    humidity_armed: bool,
+    pressure_armed: bool,
+}
+
+impl AlertState {
+    pub fn new() -> AlertState {
+        Default::default()
+    }
+}
+
+/// Returns `true` the moment `value` transitions from inside `[low, high]` to outside it, and
+/// flips back to allowing a future alert only once `value` has returned inside the bounds by more
+/// than `recover_margin`. Values that merely hover past the threshold keep `armed` set, so they
+/// don't retrigger on every call.
+fn check_metric(value: f32, low: f32, high: f32, recover_margin: f32, armed: &mut bool) -> bool {
+    let out_of_bounds = value < low || value > high;
+
+    if out_of_bounds {
+        if *armed {
+            false
+        } else {
+            *armed = true;
+            true
+        }
+    } else {
+        if value > low + recover_margin && value < high - recover_margin {
+            *armed = false;
+        }
+        false
+    }
+}
+
+/// Decides whether `msg` warrants a new alert, given configured thresholds and the previous
+/// alert state. Fires only on a transition into an out-of-bounds region for any tracked metric.
+pub fn should_alert(msg: &EspWeatherMessage, settings: &AlertSettings, state: &mut AlertState) -> bool {
+    let temp_alert = check_metric(msg.temp(), settings.temp_low, settings.temp_high, settings.temp_recover_margin, &mut state.temp_armed);
+    let humidity_alert = check_metric(msg.humidity(), settings.humidity_low, settings.humidity_high, settings.humidity_recover_margin, &mut state.humidity_armed);
+    let pressure_alert = check_metric(msg.pressure(), settings.pressure_low, settings.pressure_high, settings.pressure_recover_margin, &mut state.pressure_armed);
+
+    temp_alert || humidity_alert || pressure_alert
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn settings() -> AlertSettings {
+        AlertSettings {
+            temp_low: 15.0,
+            temp_high: 30.0,
+            humidity_low: 0.0,
+            humidity_high: 85.0,
+            pressure_low: 0.0,
+            pressure_high: 200_000.0,
+            temp_recover_margin: 2.0,
+            humidity_recover_margin: 2.0,
+            pressure_recover_margin: 200.0,
+        }
+    }
+
+    #[test]
+    fn fires_only_on_transition() {
+        let mut state = AlertState::new();
+
+        assert!(check_metric(35.0, 15.0, 30.0, 2.0, &mut state.temp_armed));
+        assert!(!check_metric(35.0, 15.0, 30.0, 2.0, &mut state.temp_armed));
+        assert!(!check_metric(36.0, 15.0, 30.0, 2.0, &mut state.temp_armed));
+    }
+
+    #[test]
+    fn requires_recover_margin_before_rearming() {
+        let mut armed = true;
+
+        assert!(!check_metric(29.0, 15.0, 30.0, 2.0, &mut armed));
+        assert!(armed);
+
+        assert!(!check_metric(27.0, 15.0, 30.0, 2.0, &mut armed));
+        assert!(!armed);
+
+        assert!(check_metric(31.0, 15.0, 30.0, 2.0, &mut armed));
+    }
+
+    #[test]
+    fn should_alert_checks_all_metrics() {
+        let mut state = AlertState::new();
+        assert!(!should_alert(&serde_json::from_str(r#"{"temp": 20.0, "pressure": 101325.0, "humidity": 40.0}"#).unwrap(), &settings(), &mut state));
+
+        assert!(should_alert(&serde_json::from_str(r#"{"temp": 35.0, "pressure": 101325.0, "humidity": 40.0}"#).unwrap(), &settings(), &mut AlertState::new()));
+        assert!(should_alert(&serde_json::from_str(r#"{"temp": 20.0, "pressure": 101325.0, "humidity": 90.0}"#).unwrap(), &settings(), &mut AlertState::new()));
+        assert!(should_alert(&serde_json::from_str(r#"{"temp": 20.0, "pressure": 250000.0, "humidity": 40.0}"#).unwrap(), &settings(), &mut AlertState::new()));
+    }
+}
@@ -8,7 +8,7 @@ use tower_web::middleware::cors::{CorsBuilder, AllowedOrigins};
 use tokio::prelude::*;
 use weather_station_bot::Settings;
 
-use db::{establish_connection, WeatherMessage, get_all_weather_messages};
+use db::{establish_connection, WeatherMessage, WeatherStats, get_all_weather_messages, median_weather};
 
 /// This type will be part of the web service as a resource.
 #[derive(Clone, Debug)]
@@ -20,6 +20,12 @@ struct WeatherMessageResponse {
     messages: Vec<WeatherMessage>
 }
 
+/// JSON response for `/stats`: one entry per day with readings in the requested window
+#[derive(Response)]
+struct WeatherStatsResponse {
+    stats: Vec<WeatherStats>
+}
+
 impl_web! {
     impl WeatherApi {
         #[get("/")]
@@ -31,6 +37,16 @@ impl_web! {
                WeatherMessageResponse { messages: weather_messages }
             )
         }
+
+        #[get("/stats")]
+        #[content_type("json")]
+        fn get_weather_stats(&self, days: i64) -> Result<WeatherStatsResponse, ()> {
+            let conn = establish_connection("./db.sqlite");
+            let stats = median_weather(days, &conn).map_err(|_| ())?;
+            Ok(
+               WeatherStatsResponse { stats }
+            )
+        }
     }
 }
 
@@ -10,13 +10,26 @@ use std::io::prelude::*;
 use tokio::sync::{mpsc::{UnboundedSender, channel}, watch};
 use std::sync::Arc;
 
-use tbot::{
-    prelude::*,
-    types::parameters::{ChatId, Text},
-};
-
-use db::{establish_connection, NewWeatherMessage, EspWeatherMessage, subscribe, unsubscribe, get_all_subscribers};
-use rumq_client::{self, eventloop, MqttOptions, QoS, Request, Subscribe, Notification};
+use tbot::prelude::*;
+
+use db::{establish_connection, NewWeatherMessage, EspWeatherMessage, subscribe, unsubscribe, get_all_slack_subscribers, subscribe_slack, unsubscribe_slack};
+use rumq_client::{self, eventloop, MqttOptions, QoS, Request, Subscribe, Publish, LastWill, Notification};
+use weather_station_bot::forecast::{self, SharedForecast};
+use weather_station_bot::pws::WindyStation;
+use weather_station_bot::alerting::{self, AlertState};
+use weather_station_bot::slack::{self, SlackClient};
+use weather_station_bot::llm;
+use weather_station_bot::mqtt::{self, Backoff};
+use weather_station_bot::publishers::{self, MastodonPublisher, Publisher, TelegramPublisher};
+use weather_station_bot::topics;
+use std::time::Instant;
+use tracing::{error, info, warn};
+
+/// A reading tagged with the sensor/location id derived from the MQTT topic it arrived on
+struct TaggedReading {
+    sensor: String,
+    message: EspWeatherMessage,
+}
 
 
 /// Helper function to read certificate files from disk
@@ -27,22 +40,47 @@ fn read_file_to_bytes(path: &str) -> Vec<u8> {
     buf
 }
 
-/// Connects to MQTT server using [Settings](settings::Settings) structure. The settings are meant to be read from config TOML file
-/// Will automatically subsribe to the topic name in the config.
-/// Subscribes to the weather topic and forwards parsed messages to tokio channel
-async fn process_mqtt_messages(settings: &Settings, tx: UnboundedSender<EspWeatherMessage>) {
-    println!(
-        "Conntcting to MQTT server at {}:{}/{}",
-        settings.mqtt.host, settings.mqtt.port, settings.mqtt.topic_name
-    );
+/// Why a `connect_and_stream` attempt ended
+enum SessionEnd {
+    /// The broker closed the stream (or it ran out of notifications); should reconnect
+    StreamClosed,
+    /// The process is shutting down (Ctrl-C); should not reconnect
+    ShuttingDown,
+}
+
+/// Builds a retained presence `Publish` for the bot's status topic
+fn status_publish(settings: &Settings, status: &str) -> Publish {
+    let mut publish = Publish::new(settings.mqtt.status_topic.clone(), QoS::AtLeastOnce, status);
+    publish.set_retain(true);
+    publish
+}
+
+/// Connects to the MQTT broker and streams notifications until the connection drops, resolving
+/// once the stream ends or Ctrl-C is received. If `tx`'s receiver has been dropped, forwarding a
+/// reading panics (see [process_message_from_device]).
+///
+/// Returns an error describing why the attempt failed so the caller can decide whether/how long
+/// to back off before retrying.
+#[tracing::instrument(skip(settings, tx), fields(host = %settings.mqtt.host, port = settings.mqtt.port))]
+async fn connect_and_stream(settings: &Settings, tx: &UnboundedSender<TaggedReading>) -> Result<SessionEnd, String> {
+    info!(topics = settings.mqtt.topics.len(), "connecting to MQTT server");
 
     // Create MQTT connection options using information from config file
     let mut mqtt_options = MqttOptions::new("weather_station_bot", settings.mqtt.host.clone(), settings.mqtt.port.clone());
     mqtt_options.set_credentials(settings.mqtt.username.clone(), settings.mqtt.password.clone());
     mqtt_options.set_inflight(10);
+    // Announce an ungraceful disconnect to anyone watching the status topic, even if the
+    // process is killed before it gets a chance to publish "offline" itself.
+    mqtt_options.set_last_will(LastWill::new(settings.mqtt.status_topic.clone(), "offline", QoS::AtLeastOnce, true));
 
     let ca_cert = read_file_to_bytes(&settings.tls.ca_cert);
     mqtt_options.set_ca(ca_cert);
+
+    // Mutual TLS is optional: only engaged when both a client cert and key are configured.
+    if let Some((client_cert, client_key)) = settings.tls.load_client_identity()? {
+        mqtt_options.set_client_auth(client_cert, client_key);
+    }
+
     mqtt_options.set_keep_alive(50);
     mqtt_options.set_throttle(std::time::Duration::from_secs(1));
 
@@ -50,62 +88,108 @@ async fn process_mqtt_messages(settings: &Settings, tx: UnboundedSender<EspWeath
     // requests_rx will be used by tokio event loop to recieve new messages
     let (mut requests_tx, requests_rx) = channel(10);
 
-    // Here we subscribe to the MQTT topic from the config file
-    let subscription = Subscribe::new(settings.mqtt.topic_name.clone(), QoS::AtLeastOnce);
-    let _ = requests_tx.send(Request::Subscribe(subscription)).await;
+    // Here we (re-)subscribe to every topic filter from the config file, each at its own QoS
+    for topic in &settings.mqtt.topics {
+        let subscription = Subscribe::new(topic.filter.clone(), mqtt::to_qos(topic.qos));
+        let _ = requests_tx.send(Request::Subscribe(subscription)).await;
+    }
 
     // And create the Tokio event loop which drives the whole message processing
     let mut event_loop = eventloop(mqtt_options, requests_rx);
-    let mut stream = event_loop.connect().await.unwrap();
+    let mut stream = event_loop
+        .connect()
+        .await
+        .map_err(|e| format!("{:?}", e))?;
+
+    let _ = requests_tx.send(Request::Publish(status_publish(settings, "online"))).await;
 
     // At last, we delegate each new message process_message_from_device function
-    println!("Waiting for notifications");
-    while let Some(notification) = stream.next().await {
-        println!("New notification — {:?}", notification);
-        process_message_from_device(&notification, &tx);
+    info!("waiting for notifications");
+    loop {
+        tokio::select! {
+            notification = stream.next() => {
+                match notification {
+                    Some(notification) => {
+                        process_message_from_device(&notification, &settings.mqtt.topics, &tx);
+                    }
+                    None => return Ok(SessionEnd::StreamClosed),
+                }
+            }
+            _ = tokio::signal::ctrl_c() => {
+                info!("shutting down, publishing offline status");
+                let _ = requests_tx.send(Request::Publish(status_publish(settings, "offline"))).await;
+                return Ok(SessionEnd::ShuttingDown);
+            }
+        }
     }
 }
 
-/// Main MQTT message processing loop. 
+/// Supervises the MQTT connection with exponential backoff: `connect_and_stream` is retried
+/// whenever the broker drops the connection or is briefly unreachable, instead of taking down
+/// the whole bot the way an unconditional `.unwrap()` on connect would.
+#[tracing::instrument(skip(settings, tx))]
+async fn process_mqtt_messages(settings: &Settings, tx: UnboundedSender<TaggedReading>) {
+    let mut backoff = Backoff::new(&settings.mqtt);
+
+    loop {
+        let connected_at = Instant::now();
+        let result = connect_and_stream(settings, &tx).await;
+        let uptime = connected_at.elapsed();
+
+        match &result {
+            Ok(SessionEnd::ShuttingDown) => return,
+            Ok(SessionEnd::StreamClosed) => warn!(?uptime, "MQTT stream ended, reconnecting"),
+            Err(e) => warn!(?uptime, error = %e, "MQTT connection error"),
+        }
+
+        backoff.note_uptime(uptime);
+
+        match backoff.next_delay() {
+            Some(delay) => {
+                info!(attempt = backoff.attempt(), ?delay, "reconnecting");
+                tokio::time::sleep(delay).await;
+            }
+            None => {
+                error!("giving up on MQTT reconnection after exceeding max_elapsed_time");
+                return;
+            }
+        }
+    }
+}
+
+/// Main MQTT message processing loop.
 ///
-/// Recieves a message from MQTT topic, deserializes it and sends it for further processing using Tokio MPSC framwrok. See [send_message_to_telegram](send_message_to_telegram)
-fn process_message_from_device(notification: &Notification, tok_tx: &UnboundedSender<EspWeatherMessage>) {
+/// Recieves a message from MQTT topic, deserializes it, tags it with the sensor id derived from
+/// the originating topic (see [topics::sensor_id_for_topic]) and sends it for further processing
+/// using Tokio MPSC framwrok. The receiving loop in `main` broadcasts it through every active
+/// [Publisher](publishers::Publisher).
+#[tracing::instrument(skip(notification, subscriptions, tok_tx))]
+fn process_message_from_device(
+    notification: &Notification,
+    subscriptions: &[topics::TopicSubscription],
+    tok_tx: &UnboundedSender<TaggedReading>,
+) {
     match notification {
         // Notification::Publish represents a message published in MQTT topic
         Notification::Publish(publish) => {
             let text: String = String::from_utf8(publish.payload.clone())
                 .expect("Can't decode payload for notification");
-            println!("Recieved message: {}", text);
 
             // As you remember, our ESP32 board encodes messages in JSON format and sends then to the MQTT server.
             // Here, we decode (deserialize) this message into Rust struct `EspWeatherMessage`
             let msg: EspWeatherMessage = serde_json::from_str(&text)
                 .expect("Error while deserializing message from ESP");
-            println!("Deserialized message: {:?}", msg);
-            println!("{}", msg);
+
+            let sensor = topics::sensor_id_for_topic(subscriptions, &publish.topic_name);
+            info!(topic = %publish.topic_name, sensor = %sensor, message = %msg, "received reading from device");
 
             // We send deserialized message via Tokio channel, that allows different coroutines to communicate between each other
-            tok_tx.send(msg).unwrap();
+            tok_tx.send(TaggedReading { sensor, message: msg }).unwrap();
         }
-        _ => println!("{:?}", notification),
+        _ => info!(?notification, "received non-publish MQTT notification"),
     }
 }
 
-/// Sends a message to subscribers
-async fn send_message_to_telegram(chat_id:i64, msg: &EspWeatherMessage, bot: &Arc<tbot::Bot>) {
-    // First, we convert EspWeatherMessage to string. Since we have implemented Diplay trait, we can just use format! macro
-    let message_str = &format!("{}", msg);
-    // Text::plain is used in tbot Telegram library to wrap plain text messages
-    let message = Text::plain(message_str);
-    println!("Sending message to Telegram");
-
-    // Here, we send the message to a subscriber's chat
-    bot.send_message(ChatId::from(chat_id), message)
-        .call()
-        .await // send_message is asynchronous, to actually call it and wait for it's result we need to use await
-        .expect("Error while sending message to the bot");
-}
-
 // Main WeatherStation Telegram bot fucntion
 #[tokio::main]
 async fn main() {
@@ -127,14 +211,66 @@ async fn main() {
         )
         .get_matches();
 
-    println!("⚠️Do not forget to make sure that you can connect to Telegram APIs. The polling module won't time out if the service is unawailable");
-    
+    // The console-subscriber feature hands task/runtime diagnostics to `tokio-console` instead
+    // of stdout, so it's mutually exclusive with our own fmt subscriber below.
+    #[cfg(feature = "console-subscriber")]
+    console_subscriber::init();
+
+    // Filtered by RUST_LOG (e.g. `RUST_LOG=weather_station_bot=debug`), falling back to "info"
+    // when the env var isn't set, so operators can dial verbosity up/down without a rebuild.
+    #[cfg(not(feature = "console-subscriber"))]
+    {
+        let filter = tracing_subscriber::EnvFilter::try_from_default_env()
+            .unwrap_or_else(|_| tracing_subscriber::EnvFilter::new("info"));
+        tracing_subscriber::fmt().with_env_filter(filter).init();
+    }
+
+    warn!("do not forget to make sure that you can connect to Telegram APIs — the polling module won't time out if the service is unawailable");
+
     // Read settings from the config file. "config" crate makes this simple
     let config = matches.value_of("config").unwrap_or("config");
-    let settings = Settings::new(config).expect("Error while reading settings");
-    
+
+    // A fresh deployment has no config file yet: write a fully-commented default and let the
+    // user fill it in, instead of hard-failing on "file not found".
+    if !std::path::Path::new(config).exists() {
+        Settings::write_default_config(config).expect("Error while writing default config file");
+        info!(path = %config, "no config file found, wrote a default one — fill it in and restart the bot");
+        return;
+    }
+
+    let mut settings = Settings::new(config).expect("Error while reading settings");
+
+    // Report every problem with the config at once rather than panicking on the first one.
+    let problems = settings.validate();
+    if !problems.is_empty() {
+        error!(path = %config, count = problems.len(), "config file has problem(s)");
+        for problem in &problems {
+            error!("  - {}", problem);
+        }
+        return;
+    }
+
+    // The Mastodon publisher needs a one-time OAuth app-registration flow before it can post;
+    // run it now and persist the resulting credentials back into the config file so a later
+    // restart picks them up instead of asking again.
+    if settings.publishers.mastodon_enabled && settings.publishers.mastodon.access_token.is_empty() {
+        match publishers::authorize(&settings.publishers.mastodon).await {
+            Ok(mastodon) => {
+                if let Err(e) = publishers::persist_mastodon_settings(config, &mastodon) {
+                    warn!(error = %e, "error while persisting Mastodon credentials");
+                }
+                settings.publishers.mastodon = mastodon;
+            }
+            Err(e) => {
+                warn!(error = %e, "error while authorizing with Mastodon, disabling it for this run");
+                settings.publishers.mastodon_enabled = false;
+            }
+        }
+    }
+
     // Structure that represents our Telegram bot.
-    // It is wrapped in an Arc (Atomic reference counter) because we will use it later in send_message_to_telegram function.
+    // It is wrapped in an Arc (Atomic reference counter) because we will use it later to build
+    // the Telegram Publisher.
     // This function is asynchronous, so Tokio could run it in a different thread.
     // Rust compiler is very smart and it won't allow us to pass values between different threads
     // without proper tracking of references and synchronization, which Arc provices for us.
@@ -142,7 +278,7 @@ async fn main() {
 
     // Tokio unbounded_channel is used to communicate between different asynchronous functions which may run in different threads.
     // Channels are like pipes: tok_tx can be used to send messages down the piple, and tok_rx can be used to recieve them
-    let (tok_tx, mut tok_rx) = tokio::sync::mpsc::unbounded_channel::<EspWeatherMessage>();
+    let (tok_tx, mut tok_rx) = tokio::sync::mpsc::unbounded_channel::<TaggedReading>();
 
     // watch::channel is a Tokio channel with a single producer and multiple consumers.
     // This is useful to share configuration (single producer) with many asynchronous functions (multiple consumers)
@@ -163,38 +299,105 @@ async fn main() {
         process_mqtt_messages(&settings, tok_tx).await;
     });
 
-    println!("Waiting for messages");   
+    // Holds the latest weather forecast, refreshed in the background by forecast::poll_forecast
+    // and read whenever we build a notification for subscribers.
+    let shared_forecast: SharedForecast = Arc::new(std::sync::Mutex::new(None));
+    let mut conf = conf_rx.clone();
+    let forecast_for_poller = shared_forecast.clone();
+    tokio::spawn(async move {
+        let settings: Settings = conf.recv().await.unwrap();
+        forecast::poll_forecast(settings.forecast, forecast_for_poller).await;
+    });
+
+    info!("waiting for messages");
     // We clone some variables since we need to move them into closure, but we allso will need them later
     // Alternatively, you can use Arc's or channels to curcumvent cloning, but I have decided to
     // make things simpler since cloning values a constant number of times at the application start
     // won't be a bottleneck in our case
     let bot_sender = bot.clone();
     let mut conf = conf_rx.clone();
+    let forecast_for_notifications = shared_forecast.clone();
     tokio::spawn(async move {
         // Here all the magic happens 🌈
         let settings: Settings = conf.recv().await.unwrap();
+        // Tracks per-metric alert state across readings so hysteresis can suppress repeat alerts
+        let mut alert_state = AlertState::new();
+
+        // The set of broadcast channels active for this run, driven by the [publishers]
+        // config section so users can enable/disable each independently.
+        let mut notification_publishers: Vec<Box<dyn Publisher>> = Vec::new();
+        if settings.publishers.telegram_enabled {
+            notification_publishers.push(Box::new(TelegramPublisher::new(bot_sender.clone(), settings.db_path.clone())));
+        }
+        if settings.publishers.mastodon_enabled {
+            notification_publishers.push(Box::new(MastodonPublisher::new(&settings.publishers.mastodon)));
+        }
+
         // Recieve new message from MQTT topic
-        while let Some(msg) = tok_rx.recv().await {
-            // Get all subrcribers from database
-            let subscribers = get_all_subscribers(&establish_connection(&settings.db_path)); 
-            println!("Recieved new message — {:?}", msg);
+        while let Some(TaggedReading { sensor, message: msg }) = tok_rx.recv().await {
+            info!(%sensor, message = ?msg, "recieved new message from sensor");
             let db_path = settings.db_path.clone();
 
-            // Send message to all active subscribers
-            for subscriber in &subscribers {
-                send_message_to_telegram(*subscriber, &msg, &bot_sender).await;
+            // Combine the sensor reading with today's forecast (if we have one yet)
+            let forecast = forecast_for_notifications.lock().unwrap().clone();
+            let mut notification = forecast::format_notification(&msg, forecast.as_ref());
+
+            if alerting::should_alert(&msg, &settings.alert, &mut alert_state) {
+                notification = format!("⚠️ Alert!\n{}", notification);
+            }
+
+            // Broadcast the notification to every active channel (Telegram, Mastodon, ...)
+            for publisher in &notification_publishers {
+                publisher.publish(&notification).await;
+            }
+
+            // Fan out the same notification to the Slack channel and DM every Slack subscriber.
+            // Only when a deployment has actually configured Slack — otherwise skip the whole
+            // block instead of POSTing with an empty token on every reading.
+            if settings.slack.enabled && !settings.slack.token.is_empty() {
+                let slack_client = SlackClient::new(&settings.slack);
+                let slack_subscribers = get_all_slack_subscribers(&establish_connection(&settings.db_path));
+
+                if let Err(e) = slack_client.post_message(&settings.slack.channel, &notification).await {
+                    warn!(error = %e, "error while posting to Slack channel");
+                }
+
+                for subscriber in &slack_subscribers {
+                    if let Err(e) = slack_client.post_message(subscriber, &notification).await {
+                        warn!(%subscriber, error = %e, "error while sending Slack DM");
+                    }
+
+                    let (status_text, status_emoji) = slack::status_from_reading(&msg);
+                    if let Err(e) = slack_client.set_status(subscriber, &status_text, status_emoji).await {
+                        warn!(%subscriber, error = %e, "error while updating Slack status");
+                    }
+                }
+            }
+
+            // Convert ESPWeatherMessage to NewWeatherMessage which can be used by diesel framework
+            // to save weather data to database, tagging it with its originating sensor
+            let new_log = NewWeatherMessage::from_esp_weather_message(&msg, &sensor);
+
+            // Optionally mirror the reading to a public PWS network. This must never block
+            // local persistence, so it runs as its own task and only logs on failure.
+            if settings.pws.enabled {
+                let pws = WindyStation::new(&settings.pws);
+                let new_log_for_upload = NewWeatherMessage::from_esp_weather_message(&msg, &sensor);
+                tokio::spawn(async move {
+                    if let Err(e) = pws.upload(&new_log_for_upload).await {
+                        warn!(error = %e, "error while uploading observation to PWS network");
+                    }
+                });
             }
 
             // Save weather data to database. Here we use a spawn blocking function to execute blocking code
             // which won't normally work in an async block
+            let save_span = tracing::info_span!("save_to_db", sensor = %sensor, timestamp = %new_log.timestamp());
             tokio::task::spawn_blocking(move || {
-                println!("Saving message to DB");
-                let connection = establish_connection(&db_path); 
-                // Convert ESPWeatherMessage to NewWeatherMessage which can be used by diesel framework
-                // to save weather data to database
-                let new_log = NewWeatherMessage::from_esp_weather_message(&msg);
+                let _enter = save_span.enter();
+                let connection = establish_connection(&db_path);
                 new_log.save_to_db(&connection).unwrap();
-                print!("Successfully saved message to DB");
+                info!("saved message to DB");
             });
         }
     });
@@ -242,6 +445,60 @@ async fn main() {
         }
     });
 
+    // Slack has no bot event loop of its own here, so subscribers link their Slack user id
+    // through the existing Telegram command dispatcher.
+    let conf = conf_rx.clone();
+    event_loop.command("link_slack", move |context| {
+        let mut conf = conf.clone();
+        async move {
+            let settings: Settings = conf.recv().await.unwrap();
+            let slack_user_id = context.text.value.trim();
+            let connection = establish_connection(&settings.db_path);
+
+            if subscribe_slack(slack_user_id, &connection).is_ok() {
+                context.send_message("Linked your Slack account").call().await.err();
+            } else {
+                context.send_message("Couldn't link that Slack user id").call().await.err();
+            }
+        }
+    });
+
+    let conf = conf_rx.clone();
+    event_loop.command("unlink_slack", move |context| {
+        let mut conf = conf.clone();
+        async move {
+            let settings: Settings = conf.recv().await.unwrap();
+            let slack_user_id = context.text.value.trim();
+            let connection = establish_connection(&settings.db_path);
+
+            if unsubscribe_slack(slack_user_id, &connection).is_ok() {
+                context.send_message("Unlinked your Slack account").call().await.err();
+            } else {
+                context.send_message("Couldn't unlink that Slack user id").call().await.err();
+            }
+        }
+    });
+
+    // Any plain-text message that isn't a recognized command is treated as a natural-language
+    // question and answered via LLM function-calling over the weather database.
+    let conf = conf_rx.clone();
+    event_loop.text(move |context| {
+        let mut conf = conf.clone();
+        async move {
+            let settings: Settings = conf.recv().await.unwrap();
+            let connection = establish_connection(&settings.db_path);
+            let question = &context.text.value;
+
+            match llm::answer_question(question, &settings.llm, &connection).await {
+                Ok(answer) => { context.send_message(&answer).call().await.err(); }
+                Err(e) => {
+                    warn!(error = %e, "error while answering question via LLM");
+                    context.send_message("Sorry, I couldn't answer that right now").call().await.err();
+                }
+            };
+        }
+    });
+
     // this starts the main event loop
     event_loop.polling().start().await.unwrap();
 }
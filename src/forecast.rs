@@ -0,0 +1,92 @@
+use crate::ForecastSettings;
+use db::EspWeatherMessage;
+use serde::{Deserialize, Serialize};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tracing::{info, warn};
+
+/// Current conditions as reported by the weather provider
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Currently {
+    pub time: i64,
+    pub summary: String,
+    pub temperature: f32,
+    pub apparent_temperature: f32,
+    pub humidity: f32,
+    pub icon: String,
+}
+
+/// A single day's worth of forecast data
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Daum {
+    pub time: i64,
+    pub temperature_high: f32,
+    pub temperature_low: f32,
+    pub icon: String,
+}
+
+/// Multi-day forecast as reported by the weather provider
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Daily {
+    pub summary: String,
+    pub data: Vec<Daum>,
+}
+
+/// A full forecast response from the provider
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Forecast {
+    pub currently: Currently,
+    pub daily: Daily,
+}
+
+/// Shared, periodically refreshed forecast that any part of the bot can read
+pub type SharedForecast = Arc<Mutex<Option<Forecast>>>;
+
+/// Fetches the latest forecast from the configured provider
+async fn fetch_forecast(settings: &ForecastSettings) -> Result<Forecast, reqwest::Error> {
+    reqwest::Client::new()
+        .get(&settings.provider_url)
+        .query(&[("key", settings.api_key.as_str()), ("location", settings.location.as_str())])
+        .send()
+        .await?
+        .json::<Forecast>()
+        .await
+}
+
+/// Periodically polls the configured weather provider and stores the latest forecast in `shared`.
+///
+/// Errors while fetching or deserializing are logged and swallowed — the last known-good forecast
+/// (if any) is kept in place so a transient provider outage doesn't blank out the notification text.
+#[tracing::instrument(skip(settings, shared))]
+pub async fn poll_forecast(settings: ForecastSettings, shared: SharedForecast) {
+    let mut interval = tokio::time::interval(Duration::from_secs(settings.poll_interval_secs));
+
+    loop {
+        interval.tick().await;
+
+        match fetch_forecast(&settings).await {
+            Ok(forecast) => {
+                info!(summary = %forecast.daily.summary, "updated forecast");
+                *shared.lock().unwrap() = Some(forecast);
+            }
+            Err(e) => warn!(error = %e, "error while polling weather forecast, keeping last known forecast"),
+        }
+    }
+}
+
+/// Builds the notification text sent to subscribers: the current sensor reading followed by
+/// today's high/low and summary, when a forecast is available.
+pub fn format_notification(msg: &EspWeatherMessage, forecast: Option<&Forecast>) -> String {
+    let mut text = format!("{}", msg);
+
+    if let Some(forecast) = forecast {
+        if let Some(today) = forecast.daily.data.first() {
+            text.push_str(&format!(
+                "\n\nToday: {}\n⬆️{:>6.1}℃ ⬇️{:>6.1}℃",
+                forecast.daily.summary, today.temperature_high, today.temperature_low
+            ));
+        }
+    }
+
+    text
+}
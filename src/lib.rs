@@ -1,13 +1,32 @@
 use serde::{Serialize, Deserialize};
 use config::{self, ConfigError, };
+use tracing::info;
+
+pub mod alerting;
+pub mod forecast;
+pub mod llm;
+pub mod mqtt;
+pub mod publishers;
+pub mod pws;
+pub mod slack;
+pub mod topics;
 
 #[derive(Serialize, Deserialize, Clone)]
 pub struct MQTTSettings {
     pub host: String,
     pub port: u16,
-    pub topic_name: String,
+    /// Topic filters to subscribe to, each with its own QoS. Supports `+`/`#` wildcards.
+    pub topics: Vec<topics::TopicSubscription>,
     pub username: String,
-    pub password: String
+    pub password: String,
+    /// Delay before the first reconnect attempt, in milliseconds
+    pub initial_interval_ms: u64,
+    /// Upper bound the exponential backoff delay is capped at, in milliseconds
+    pub max_interval_ms: u64,
+    /// Give up retrying after this many milliseconds of continuous failure (0 = retry forever)
+    pub max_elapsed_time_ms: u64,
+    /// Topic the bot publishes its retained online/offline presence to, e.g. `<topic_name>/bot_status`
+    pub status_topic: String,
 }
 
 #[derive(Serialize, Deserialize, Clone)]
@@ -17,7 +36,98 @@ pub struct TelegramSettings {
 
 #[derive(Serialize, Deserialize, Clone)]
 pub struct TLSSettings {
-    pub ca_cert: String
+    pub ca_cert: String,
+    /// Client certificate for mutual TLS, in addition to username/password auth. Optional, but
+    /// must be set together with `client_key` (see `load_client_identity`).
+    #[serde(default)]
+    pub client_cert: String,
+    /// Private key matching `client_cert`. Optional, but must be set together with `client_cert`.
+    #[serde(default)]
+    pub client_key: String,
+}
+
+impl TLSSettings {
+    /// Loads the client certificate/key pair for mutual TLS, if configured. Returns `None` when
+    /// neither path is set (password-only auth); returns an error if only one of the two is set,
+    /// since a cert without its key (or vice versa) can't be used to complete a TLS handshake.
+    pub fn load_client_identity(&self) -> Result<Option<(Vec<u8>, Vec<u8>)>, String> {
+        match (self.client_cert.is_empty(), self.client_key.is_empty()) {
+            (true, true) => Ok(None),
+            (false, false) => Ok(Some((
+                std::fs::read(&self.client_cert)
+                    .map_err(|e| format!("Error while reading client_cert '{}': {}", self.client_cert, e))?,
+                std::fs::read(&self.client_key)
+                    .map_err(|e| format!("Error while reading client_key '{}': {}", self.client_key, e))?,
+            ))),
+            _ => Err("tls.client_cert and tls.client_key must both be set, or both left empty".to_string()),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct ForecastSettings {
+    pub provider_url: String,
+    pub api_key: String,
+    pub poll_interval_secs: u64,
+    pub location: String,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct PwsSettings {
+    pub enabled: bool,
+    pub api_key: String,
+    pub station_id: String,
+    pub endpoint_url: String,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct AlertSettings {
+    pub temp_low: f32,
+    pub temp_high: f32,
+    pub humidity_low: f32,
+    pub humidity_high: f32,
+    pub pressure_low: f32,
+    pub pressure_high: f32,
+    /// How far temperature must fall back inside its bounds before the alert re-arms
+    pub temp_recover_margin: f32,
+    /// How far humidity must fall back inside its bounds before the alert re-arms
+    pub humidity_recover_margin: f32,
+    /// How far pressure must fall back inside its bounds before the alert re-arms. Pressure is
+    /// tracked in Pascals, so this needs to be on a much larger scale than the temperature and
+    /// humidity margins.
+    pub pressure_recover_margin: f32,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct SlackSettings {
+    pub enabled: bool,
+    pub token: String,
+    pub channel: String,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct LlmSettings {
+    pub endpoint: String,
+    pub api_key: String,
+    pub model: String,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct MastodonSettings {
+    /// Base URL of the Fediverse instance to post to, e.g. `https://mastodon.social`
+    pub instance_url: String,
+    /// Obtained (and persisted back into the config file) by the one-time OAuth app
+    /// registration flow; left empty until then.
+    pub client_id: String,
+    pub client_secret: String,
+    pub access_token: String,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct PublisherSettings {
+    pub telegram_enabled: bool,
+    pub mastodon_enabled: bool,
+    pub mastodon: MastodonSettings,
 }
 
 #[derive(Serialize, Deserialize, Clone)]
@@ -26,14 +136,151 @@ pub struct Settings {
     pub mqtt: MQTTSettings,
     pub telegram: TelegramSettings,
     pub tls: TLSSettings,
+    pub forecast: ForecastSettings,
+    pub pws: PwsSettings,
+    pub alert: AlertSettings,
+    pub slack: SlackSettings,
+    pub llm: LlmSettings,
+    pub publishers: PublisherSettings,
 }
 
+/// A fully-commented default config, written out by [Settings::write_default_config] so a fresh
+/// deployment has something to fill in instead of hitting a bare "file not found" error.
+const DEFAULT_CONFIG: &str = r#"# weather-station-bot configuration
+# Fill in the placeholders below, then restart the bot.
+
+# Path to the SQLite (or Postgres connection string, if built with the "postgres" feature) database
+db_path = "weather_station.db"
+
+[mqtt]
+host = "mqtt.example.com"
+port = 8883
+username = ""
+password = ""
+# One entry per topic filter to subscribe to; each can use MQTT's `+`/`#` wildcards and its own QoS
+topics = [
+    { filter = "sensors/+/weather", qos = 1 },
+]
+initial_interval_ms = 500
+max_interval_ms = 60000
+max_elapsed_time_ms = 0
+status_topic = "weather_station_bot/status"
+
+[telegram]
+# Token from @BotFather
+token = "REPLACE_WITH_TELEGRAM_BOT_TOKEN"
+
+[tls]
+ca_cert = "/path/to/ca.pem"
+# client_cert/client_key are optional and only needed for mutual TLS; leave both empty to skip
+client_cert = ""
+client_key = ""
+
+[forecast]
+provider_url = "https://api.example.com/forecast"
+api_key = ""
+poll_interval_secs = 3600
+location = ""
+
+[pws]
+enabled = false
+api_key = ""
+station_id = ""
+endpoint_url = "https://weatherstation.wunderground.com/weatherstation/updateweatherstation.php"
+
+[alert]
+temp_low = -20.0
+temp_high = 35.0
+humidity_low = 10.0
+humidity_high = 90.0
+pressure_low = 98000.0
+pressure_high = 105000.0
+temp_recover_margin = 1.0
+humidity_recover_margin = 1.0
+pressure_recover_margin = 100.0
+
+[slack]
+enabled = false
+token = ""
+channel = ""
+
+[llm]
+endpoint = "https://api.openai.com/v1/chat/completions"
+api_key = ""
+model = "gpt-4o-mini"
+
+[publishers]
+telegram_enabled = true
+mastodon_enabled = false
+
+[publishers.mastodon]
+instance_url = ""
+client_id = ""
+client_secret = ""
+access_token = ""
+"#;
+
 impl Settings {
    /// Read settings from the config file
    pub fn new(config_path: &str) -> Result<Self, ConfigError> {
     let mut settings = config::Config::default();
-    println!("Reading config file");
+    info!(path = %config_path, "reading config file");
     settings.merge(config::File::with_name(config_path)).unwrap();
     settings.try_into()
    }
+
+   /// Writes a fully-commented default config to `config_path`, for a first run where the file
+   /// doesn't exist yet. Fails if a file is already there, so it can never clobber a real config.
+   pub fn write_default_config(config_path: &str) -> std::io::Result<()> {
+       use std::io::Write;
+
+       std::fs::OpenOptions::new()
+           .write(true)
+           .create_new(true)
+           .open(config_path)?
+           .write_all(DEFAULT_CONFIG.as_bytes())
+   }
+
+   /// Upfront validation that reports every problem with the settings at once (missing token,
+   /// unreadable CA cert, ...) instead of panicking on whichever one happens to be hit first.
+   pub fn validate(&self) -> Vec<String> {
+       let mut problems = Vec::new();
+
+       if self.telegram.token.is_empty() || self.telegram.token == "REPLACE_WITH_TELEGRAM_BOT_TOKEN" {
+           problems.push("telegram.token is not set".to_string());
+       }
+
+       if self.mqtt.topics.is_empty() {
+           problems.push("mqtt.topics has no entries".to_string());
+       }
+
+       if let Err(e) = std::fs::read(&self.tls.ca_cert) {
+           problems.push(format!("tls.ca_cert '{}' is not readable: {}", self.tls.ca_cert, e));
+       }
+
+       if let Err(e) = self.tls.load_client_identity() {
+           problems.push(e);
+       }
+
+       match std::path::Path::new(&self.db_path).parent() {
+           Some(dir) if !dir.as_os_str().is_empty() && !dir.exists() => {
+               problems.push(format!("db_path's parent directory '{}' does not exist", dir.display()));
+           }
+           _ => {}
+       }
+
+       if self.publishers.mastodon_enabled && self.publishers.mastodon.instance_url.is_empty() {
+           problems.push("publishers.mastodon_enabled is true but publishers.mastodon.instance_url is not set".to_string());
+       }
+
+       if self.slack.enabled && self.slack.token.is_empty() {
+           problems.push("slack.enabled is true but slack.token is not set".to_string());
+       }
+
+       if self.forecast.poll_interval_secs == 0 {
+           problems.push("forecast.poll_interval_secs must be greater than 0".to_string());
+       }
+
+       problems
+   }
 }
@@ -0,0 +1,111 @@
+use crate::LlmSettings;
+use db::{get_all_weather_messages, get_latest_reading, median_weather, DbConnection};
+use serde_json::{json, Value};
+
+/// Caps how many tool-call round trips a single question can take before we give up and return
+/// a fixed fallback reply, to avoid a runaway loop against the LLM endpoint.
+const MAX_ITERATIONS: u8 = 5;
+
+/// Smallest and largest `days` window accepted by the `get_weather_stats` tool, independent of
+/// whatever the model asks for.
+const MIN_STATS_DAYS: i64 = 1;
+const MAX_STATS_DAYS: i64 = 90;
+
+/// JSON schemas for the tools the model is allowed to call, in OpenAI-style function-calling
+/// format. Each one wraps an existing DB accessor.
+fn tool_schemas() -> Value {
+    json!([
+        {
+            "type": "function",
+            "function": {
+                "name": "get_latest_reading",
+                "description": "Get the most recent temperature/pressure/humidity reading",
+                "parameters": { "type": "object", "properties": {} },
+            },
+        },
+        {
+            "type": "function",
+            "function": {
+                "name": "get_all_weather_messages",
+                "description": "Get every stored weather reading, newest first",
+                "parameters": { "type": "object", "properties": {} },
+            },
+        },
+        {
+            "type": "function",
+            "function": {
+                "name": "get_weather_stats",
+                "description": "Get per-day min/max/mean/median temperature, pressure and humidity for the last N days",
+                "parameters": {
+                    "type": "object",
+                    "properties": { "days": { "type": "integer", "description": "How many days back to aggregate" } },
+                    "required": ["days"],
+                },
+            },
+        },
+    ])
+}
+
+/// Runs a tool call against the database, clamping/validating arguments first so a misbehaving
+/// model can't make the bot do something unreasonable (e.g. aggregate a century of history).
+fn dispatch_tool(name: &str, arguments: &Value, connection: &DbConnection) -> Value {
+    match name {
+        "get_latest_reading" => json!(get_latest_reading(connection).unwrap_or(None)),
+        "get_all_weather_messages" => json!(get_all_weather_messages(connection)),
+        "get_weather_stats" => {
+            let days = arguments
+                .get("days")
+                .and_then(Value::as_i64)
+                .unwrap_or(MIN_STATS_DAYS)
+                .clamp(MIN_STATS_DAYS, MAX_STATS_DAYS);
+
+            json!(median_weather(days, connection).unwrap_or_default())
+        }
+        _ => json!({ "error": format!("unknown tool: {}", name) }),
+    }
+}
+
+/// Answers a free-form question by letting a chat-completion model call the tools above,
+/// looping until it returns a plain message or `MAX_ITERATIONS` is reached.
+pub async fn answer_question(question: &str, settings: &LlmSettings, connection: &DbConnection) -> Result<String, reqwest::Error> {
+    let client = reqwest::Client::new();
+    let mut messages = vec![json!({ "role": "user", "content": question })];
+
+    for _ in 0..MAX_ITERATIONS {
+        let response: Value = client
+            .post(&settings.endpoint)
+            .bearer_auth(&settings.api_key)
+            .json(&json!({ "model": settings.model, "messages": messages, "tools": tool_schemas() }))
+            .send()
+            .await?
+            .json()
+            .await?;
+
+        let choice = &response["choices"][0]["message"];
+        let tool_calls = choice["tool_calls"].as_array().cloned().unwrap_or_default();
+
+        if tool_calls.is_empty() {
+            return Ok(choice["content"].as_str().unwrap_or_default().to_string());
+        }
+
+        messages.push(choice.clone());
+
+        for tool_call in tool_calls {
+            let name = tool_call["function"]["name"].as_str().unwrap_or_default();
+            let arguments: Value = tool_call["function"]["arguments"]
+                .as_str()
+                .and_then(|s| serde_json::from_str(s).ok())
+                .unwrap_or(json!({}));
+
+            let result = dispatch_tool(name, &arguments, connection);
+
+            messages.push(json!({
+                "role": "tool",
+                "tool_call_id": tool_call["id"],
+                "content": result.to_string(),
+            }));
+        }
+    }
+
+    Ok("I couldn't figure that out in time, please try rephrasing your question.".to_string())
+}
@@ -0,0 +1,130 @@
+use crate::MQTTSettings;
+use rumq_client::QoS;
+use std::time::{Duration, Instant};
+
+/// Converts a numeric QoS level (0/1/2, as read from config) to `rumq_client`'s `QoS` enum,
+/// falling back to `AtMostOnce` for anything out of range.
+pub fn to_qos(level: u8) -> QoS {
+    match level {
+        1 => QoS::AtLeastOnce,
+        2 => QoS::ExactlyOnce,
+        _ => QoS::AtMostOnce,
+    }
+}
+
+/// How long a connection has to stay up before the backoff delay resets to its initial value.
+const RESET_AFTER: Duration = Duration::from_secs(60);
+
+/// Exponential backoff with jitter for MQTT reconnection.
+///
+/// Each failed connect/stream-error doubles the delay (capped at `max_interval`); a connection
+/// that stays up past [`RESET_AFTER`] resets the delay back to `initial_interval` so a brief
+/// blip doesn't leave the bot backing off slowly forever.
+pub struct Backoff {
+    initial_interval: Duration,
+    max_interval: Duration,
+    max_elapsed_time: Duration,
+    current_interval: Duration,
+    attempt: u32,
+    started_at: Instant,
+}
+
+impl Backoff {
+    pub fn new(settings: &MQTTSettings) -> Backoff {
+        let initial_interval = Duration::from_millis(settings.initial_interval_ms);
+
+        Backoff {
+            initial_interval,
+            max_interval: Duration::from_millis(settings.max_interval_ms),
+            max_elapsed_time: Duration::from_millis(settings.max_elapsed_time_ms),
+            current_interval: initial_interval,
+            attempt: 0,
+            started_at: Instant::now(),
+        }
+    }
+
+    /// Returns the delay to wait before the next reconnect attempt, with random jitter applied,
+    /// and advances the backoff state. `None` once `max_elapsed_time` has been exceeded (unless
+    /// it is zero, meaning "retry forever").
+    pub fn next_delay(&mut self) -> Option<Duration> {
+        if !self.max_elapsed_time.is_zero() && self.started_at.elapsed() > self.max_elapsed_time {
+            return None;
+        }
+
+        self.attempt += 1;
+        let delay = self.current_interval;
+
+        self.current_interval = (self.current_interval * 2).min(self.max_interval);
+
+        let jitter_ms = (delay.as_millis() as f64 * rand_fraction()) as u64;
+        Some(delay + Duration::from_millis(jitter_ms))
+    }
+
+    /// Call after each connection attempt ends, with how long it lasted; resets the backoff state
+    /// if that was longer than `RESET_AFTER`.
+    pub fn note_uptime(&mut self, uptime: Duration) {
+        if uptime > RESET_AFTER {
+            self.current_interval = self.initial_interval;
+            self.attempt = 0;
+            self.started_at = Instant::now();
+        }
+    }
+
+    pub fn attempt(&self) -> u32 {
+        self.attempt
+    }
+}
+
+/// A small pseudo-random fraction in `[0.0, 1.0)` used to jitter reconnect delays, without
+/// pulling in a dedicated RNG crate for a single call site.
+fn rand_fraction() -> f64 {
+    use std::collections::hash_map::RandomState;
+    use std::hash::{BuildHasher, Hasher};
+
+    (RandomState::new().build_hasher().finish() % 1000) as f64 / 1000.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn settings() -> MQTTSettings {
+        MQTTSettings {
+            host: "localhost".into(),
+            port: 1883,
+            topics: vec![crate::topics::TopicSubscription { filter: "test".into(), qos: 1 }],
+            username: "".into(),
+            password: "".into(),
+            initial_interval_ms: 500,
+            max_interval_ms: 60_000,
+            max_elapsed_time_ms: 0,
+            status_topic: "test/bot_status".into(),
+        }
+    }
+
+    #[test]
+    fn doubles_up_to_the_cap() {
+        let mut backoff = Backoff::new(&settings());
+
+        let first = backoff.next_delay().unwrap();
+        let second = backoff.next_delay().unwrap();
+
+        assert!(first >= Duration::from_millis(500));
+        assert!(second >= Duration::from_millis(1000));
+
+        for _ in 0..20 {
+            backoff.next_delay();
+        }
+        assert_eq!(backoff.current_interval, Duration::from_millis(60_000));
+    }
+
+    #[test]
+    fn resets_after_sustained_uptime() {
+        let mut backoff = Backoff::new(&settings());
+        backoff.next_delay();
+        backoff.next_delay();
+
+        backoff.note_uptime(Duration::from_secs(120));
+        assert_eq!(backoff.current_interval, backoff.initial_interval);
+    }
+}
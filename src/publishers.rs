@@ -0,0 +1,188 @@
+use crate::MastodonSettings;
+use async_trait::async_trait;
+use db::{establish_connection, get_all_subscribers};
+use std::io::{self, Write};
+use std::sync::Arc;
+use tbot::{
+    prelude::*,
+    types::parameters::{ChatId, Text},
+};
+use tracing::warn;
+
+/// Something that can broadcast a formatted weather notification to subscribers on one channel.
+/// Implemented by [TelegramPublisher] and [MastodonPublisher] so `main` can drive an arbitrary
+/// set of active channels instead of a hard-coded call to Telegram.
+#[async_trait]
+pub trait Publisher {
+    async fn publish(&self, message: &str);
+}
+
+/// Fans a notification out to every chat id stored in the database via Telegram
+pub struct TelegramPublisher {
+    bot: Arc<tbot::Bot>,
+    db_path: String,
+}
+
+impl TelegramPublisher {
+    pub fn new(bot: Arc<tbot::Bot>, db_path: String) -> TelegramPublisher {
+        TelegramPublisher { bot, db_path }
+    }
+}
+
+#[async_trait]
+impl Publisher for TelegramPublisher {
+    async fn publish(&self, message: &str) {
+        let subscribers = get_all_subscribers(&establish_connection(&self.db_path));
+
+        for chat_id in &subscribers {
+            if let Err(e) = self
+                .bot
+                .send_message(ChatId::from(*chat_id), Text::plain(message))
+                .call()
+                .await
+            {
+                warn!(%chat_id, error = ?e, "error while sending message to Telegram chat");
+            }
+        }
+    }
+}
+
+/// Posts a notification as a status on a Fediverse instance, turning the bot into a
+/// general-purpose weather-broadcast hub rather than a Telegram-only one.
+pub struct MastodonPublisher {
+    instance_url: String,
+    access_token: String,
+    client: reqwest::Client,
+}
+
+impl MastodonPublisher {
+    pub fn new(settings: &MastodonSettings) -> MastodonPublisher {
+        MastodonPublisher {
+            instance_url: settings.instance_url.clone(),
+            access_token: settings.access_token.clone(),
+            client: reqwest::Client::new(),
+        }
+    }
+}
+
+#[async_trait]
+impl Publisher for MastodonPublisher {
+    async fn publish(&self, message: &str) {
+        let result = self
+            .client
+            .post(&format!("{}/api/v1/statuses", self.instance_url))
+            .bearer_auth(&self.access_token)
+            .json(&serde_json::json!({ "status": message }))
+            .send()
+            .await
+            .and_then(|r| r.error_for_status());
+
+        if let Err(e) = result {
+            warn!(error = %e, "error while posting status to Mastodon");
+        }
+    }
+}
+
+/// Response body of `POST /api/v1/apps`
+#[derive(serde::Deserialize)]
+struct RegisteredApp {
+    client_id: String,
+    client_secret: String,
+}
+
+/// Response body of `POST /oauth/token`
+#[derive(serde::Deserialize)]
+struct TokenResponse {
+    access_token: String,
+}
+
+/// `redirect_uri` for the out-of-band flow: the user is shown a code on the instance's own page
+/// instead of being redirected, since the bot has no webserver of its own to receive a callback.
+const OOB_REDIRECT_URI: &str = "urn:ietf:wg:oauth:2.0:oob";
+
+/// Runs the one-time OAuth app-registration flow for a Mastodon/Fediverse instance: registers
+/// an app (if `client_id`/`client_secret` aren't already known), prints the authorize URL,
+/// reads the code the user pastes back from stdin, and exchanges it for an access token.
+///
+/// Returns the settings with `client_id`/`client_secret`/`access_token` filled in; the caller is
+/// responsible for persisting them back into the config file with [persist_mastodon_settings] so
+/// this flow doesn't have to run again on the next start.
+pub async fn authorize(settings: &MastodonSettings) -> Result<MastodonSettings, String> {
+    let client = reqwest::Client::new();
+    let mut settings = settings.clone();
+
+    if settings.client_id.is_empty() || settings.client_secret.is_empty() {
+        let app: RegisteredApp = client
+            .post(&format!("{}/api/v1/apps", settings.instance_url))
+            .form(&[
+                ("client_name", "weather-station-bot"),
+                ("redirect_uris", OOB_REDIRECT_URI),
+                ("scopes", "write"),
+            ])
+            .send()
+            .await
+            .map_err(|e| format!("Error while registering Mastodon app: {}", e))?
+            .json()
+            .await
+            .map_err(|e| format!("Error while parsing Mastodon app registration response: {}", e))?;
+
+        settings.client_id = app.client_id;
+        settings.client_secret = app.client_secret;
+    }
+
+    println!(
+        "Open this URL, approve access, and paste the code it gives you:\n{}/oauth/authorize?client_id={}&redirect_uri={}&response_type=code&scope=write",
+        settings.instance_url, settings.client_id, OOB_REDIRECT_URI
+    );
+    print!("Code: ");
+    io::stdout().flush().ok();
+
+    let mut code = String::new();
+    io::stdin()
+        .read_line(&mut code)
+        .map_err(|e| format!("Error while reading authorization code: {}", e))?;
+
+    let token: TokenResponse = client
+        .post(&format!("{}/oauth/token", settings.instance_url))
+        .form(&[
+            ("client_id", settings.client_id.as_str()),
+            ("client_secret", settings.client_secret.as_str()),
+            ("redirect_uri", OOB_REDIRECT_URI),
+            ("grant_type", "authorization_code"),
+            ("code", code.trim()),
+            ("scope", "write"),
+        ])
+        .send()
+        .await
+        .map_err(|e| format!("Error while exchanging Mastodon authorization code: {}", e))?
+        .json()
+        .await
+        .map_err(|e| format!("Error while parsing Mastodon token response: {}", e))?;
+
+    settings.access_token = token.access_token;
+    Ok(settings)
+}
+
+/// Writes the `[publishers.mastodon]` section of `config_path` back to disk with the values
+/// from `settings`, so the app registration and access token obtained via [authorize] survive a
+/// restart instead of re-running the OAuth flow every time.
+pub fn persist_mastodon_settings(config_path: &str, settings: &MastodonSettings) -> Result<(), String> {
+    let contents = std::fs::read_to_string(config_path)
+        .map_err(|e| format!("Error while reading config file to persist Mastodon token: {}", e))?;
+
+    let mut doc: toml::Value = contents
+        .parse()
+        .map_err(|e| format!("Error while parsing config file to persist Mastodon token: {}", e))?;
+
+    let mastodon = doc
+        .get_mut("publishers")
+        .and_then(|p| p.get_mut("mastodon"))
+        .ok_or_else(|| "config file has no [publishers.mastodon] section".to_string())?;
+
+    mastodon["client_id"] = toml::Value::String(settings.client_id.clone());
+    mastodon["client_secret"] = toml::Value::String(settings.client_secret.clone());
+    mastodon["access_token"] = toml::Value::String(settings.access_token.clone());
+
+    std::fs::write(config_path, toml::to_string_pretty(&doc).map_err(|e| e.to_string())?)
+        .map_err(|e| format!("Error while writing config file to persist Mastodon token: {}", e))
+}
@@ -0,0 +1,49 @@
+use crate::PwsSettings;
+use db::NewWeatherMessage;
+
+/// Uploads observations to a personal-weather-station network (Windy/WU-style).
+///
+/// Kept as a thin client so it can be swapped out or disabled without touching the MQTT
+/// handling code: callers should check `PwsSettings::enabled` before constructing one.
+pub struct WindyStation {
+    api_key: String,
+    station_id: String,
+    endpoint_url: String,
+    client: reqwest::Client,
+}
+
+impl WindyStation {
+    pub fn new(settings: &PwsSettings) -> WindyStation {
+        WindyStation {
+            api_key: settings.api_key.clone(),
+            station_id: settings.station_id.clone(),
+            endpoint_url: settings.endpoint_url.clone(),
+            client: reqwest::Client::new(),
+        }
+    }
+
+    /// Uploads a single observation. Failures are returned to the caller to log — they must
+    /// never block local persistence of the same reading.
+    pub async fn upload(&self, msg: &NewWeatherMessage) -> Result<(), reqwest::Error> {
+        const PA_TO_MM_MERCURY: f32 = 133.322;
+
+        self.client
+            .post(&self.endpoint_url)
+            .query(&[
+                ("station_id", self.station_id.as_str()),
+                ("key", self.api_key.as_str()),
+            ])
+            .json(&serde_json::json!({
+                "dateutc": msg.timestamp(),
+                "tempf": msg.temp() * 9.0 / 5.0 + 32.0,
+                "humidity": msg.humidity(),
+                // `pressure()` is in Pascals (see db::NewWeatherMessage); Pa -> mmHg -> inHg.
+                "baromin": msg.pressure() / PA_TO_MM_MERCURY / 25.4,
+            }))
+            .send()
+            .await?
+            .error_for_status()?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,67 @@
+use crate::SlackSettings;
+use db::EspWeatherMessage;
+
+/// Thin client for the Slack Web API, used to deliver weather alerts and reflect current
+/// conditions in a subscriber's status, mirroring the role `tbot::Bot` plays for Telegram.
+pub struct SlackClient {
+    token: String,
+    client: reqwest::Client,
+}
+
+impl SlackClient {
+    pub fn new(settings: &SlackSettings) -> SlackClient {
+        SlackClient {
+            token: settings.token.clone(),
+            client: reqwest::Client::new(),
+        }
+    }
+
+    /// Posts `text` to a channel or user (`chat.postMessage` accepts both as `channel`)
+    pub async fn post_message(&self, channel: &str, text: &str) -> Result<(), reqwest::Error> {
+        self.client
+            .post("https://slack.com/api/chat.postMessage")
+            .bearer_auth(&self.token)
+            .json(&serde_json::json!({ "channel": channel, "text": text }))
+            .send()
+            .await?
+            .error_for_status()?;
+
+        Ok(())
+    }
+
+    /// Sets a subscriber's Slack status text/emoji, e.g. from `temp_to_emoji`/`humidity_to_emoji`
+    /// on each new reading.
+    pub async fn set_status(&self, user_id: &str, status_text: &str, status_emoji: &str) -> Result<(), reqwest::Error> {
+        self.client
+            .post("https://slack.com/api/users.profile.set")
+            .bearer_auth(&self.token)
+            .json(&serde_json::json!({
+                "user": user_id,
+                "profile": { "status_text": status_text, "status_emoji": status_emoji },
+            }))
+            .send()
+            .await?
+            .error_for_status()?;
+
+        Ok(())
+    }
+}
+
+/// Maps `EspWeatherMessage::temp_to_emoji`'s glyph to the `:shortcode:` Slack's
+/// `users.profile.set` expects for `status_emoji` (it rejects raw Unicode emoji).
+fn temp_emoji_shortcode(glyph: &str) -> &'static str {
+    match glyph {
+        "🥶" => ":cold_face:",
+        "❄️" => ":snowflake:",
+        "☀️" => ":sunny:",
+        "🔥" => ":fire:",
+        _ => "",
+    }
+}
+
+/// Builds a short Slack status line from `msg`, staying well under the 100-character limit
+/// `users.profile.set` enforces on `status_text`, paired with a real shortcode for `status_emoji`.
+pub fn status_from_reading(msg: &EspWeatherMessage) -> (String, &'static str) {
+    let status_text = format!("{:.1}℃, {:.0}% humidity", msg.temp(), msg.humidity());
+    (status_text, temp_emoji_shortcode(msg.temp_to_emoji()))
+}
@@ -0,0 +1,86 @@
+use serde::{Deserialize, Serialize};
+
+/// One subscribed topic filter and the QoS it should be subscribed at
+#[derive(Serialize, Deserialize, Clone)]
+pub struct TopicSubscription {
+    pub filter: String,
+    pub qos: u8,
+}
+
+/// Returns true if `topic` matches the MQTT filter `pattern`, honouring the `+`
+/// (single-level) and `#` (multi-level, trailing only) wildcards.
+pub fn topic_matches(pattern: &str, topic: &str) -> bool {
+    let pattern_segments: Vec<&str> = pattern.split('/').collect();
+    let topic_segments: Vec<&str> = topic.split('/').collect();
+
+    for (i, p) in pattern_segments.iter().enumerate() {
+        if *p == "#" {
+            return true;
+        }
+
+        match topic_segments.get(i) {
+            Some(t) if *p == "+" || p == t => continue,
+            _ => return false,
+        }
+    }
+
+    pattern_segments.len() == topic_segments.len()
+}
+
+/// Derives a sensor/location identifier for `topic` from the first configured filter it
+/// matches: the segment(s) that landed on a `+` wildcard, joined with `/`, or the full topic
+/// when the matching filter has no wildcard segments.
+pub fn sensor_id_for_topic(subscriptions: &[TopicSubscription], topic: &str) -> String {
+    for subscription in subscriptions {
+        if !topic_matches(&subscription.filter, topic) {
+            continue;
+        }
+
+        let pattern_segments: Vec<&str> = subscription.filter.split('/').collect();
+        let topic_segments: Vec<&str> = topic.split('/').collect();
+
+        let wildcard_segments: Vec<&str> = pattern_segments
+            .iter()
+            .zip(topic_segments.iter())
+            .filter(|(p, _)| **p == "+")
+            .map(|(_, t)| *t)
+            .collect();
+
+        return if wildcard_segments.is_empty() {
+            topic.to_string()
+        } else {
+            wildcard_segments.join("/")
+        };
+    }
+
+    topic.to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn matches_plus_wildcard() {
+        assert!(topic_matches("sensors/+/weather", "sensors/attic/weather"));
+        assert!(!topic_matches("sensors/+/weather", "sensors/attic/co2/weather"));
+    }
+
+    #[test]
+    fn matches_hash_wildcard() {
+        assert!(topic_matches("sensors/#", "sensors/attic/weather"));
+        assert!(!topic_matches("sensors/#", "other/attic/weather"));
+    }
+
+    #[test]
+    fn derives_sensor_id_from_wildcard_segment() {
+        let subscriptions = vec![TopicSubscription { filter: "sensors/+/weather".into(), qos: 1 }];
+        assert_eq!(sensor_id_for_topic(&subscriptions, "sensors/attic/weather"), "attic");
+    }
+
+    #[test]
+    fn falls_back_to_full_topic_without_wildcard() {
+        let subscriptions = vec![TopicSubscription { filter: "sensors/attic/weather".into(), qos: 1 }];
+        assert_eq!(sensor_id_for_topic(&subscriptions, "sensors/attic/weather"), "sensors/attic/weather");
+    }
+}